@@ -0,0 +1,138 @@
+// Copyright 2018-2020 the Deno authors. All rights reserved. MIT license.
+use super::tty::record_tty_write;
+use crate::state::State;
+use deno_core::CoreIsolate;
+use deno_core::ErrBox;
+use deno_core::ResourceTable;
+use deno_core::ZeroCopyBuf;
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::rc::Rc;
+
+#[cfg(unix)]
+use nix::sys::termios::Termios;
+
+#[derive(Default)]
+pub struct TtyMetadata {
+  #[cfg(unix)]
+  pub mode: Option<Termios>,
+}
+
+#[derive(Default)]
+pub struct FileMetadata {
+  pub tty: TtyMetadata,
+}
+
+#[derive(Default)]
+pub struct StdinMetadata {
+  #[cfg(unix)]
+  pub mode: Option<Termios>,
+}
+
+pub enum StreamResource {
+  Stdin(tokio::io::Stdin, StdinMetadata),
+  Stdout(tokio::io::Stdout),
+  Stderr(tokio::io::Stderr),
+  FsFile(Option<(tokio::fs::File, FileMetadata)>),
+}
+
+pub struct StreamResourceHolder {
+  pub resource: StreamResource,
+}
+
+impl StreamResourceHolder {
+  pub fn new(resource: StreamResource) -> Self {
+    Self { resource }
+  }
+}
+
+// Runs `f` with a `&mut std::fs::File` for fs-backed resources, doing the
+// same take/try_into_std/put-back dance used throughout `ops::tty`. Stdin,
+// stdout and stderr can't be borrowed out as a `std::fs::File`, so they're
+// handed back to `f` as `Err(StreamResource)` (a fresh handle to the same
+// underlying fd/stream, since these are thin singleton wrappers) for the
+// caller to special-case.
+pub fn std_file_resource<F, T>(
+  resource_table: &mut ResourceTable,
+  rid: u32,
+  f: F,
+) -> Result<T, ErrBox>
+where
+  F: FnOnce(Result<&mut std::fs::File, StreamResource>) -> Result<T, ErrBox>,
+{
+  let resource_holder = resource_table
+    .get_mut::<StreamResourceHolder>(rid)
+    .ok_or_else(ErrBox::bad_resource_id)?;
+
+  if let StreamResource::FsFile(option_file_metadata) =
+    &mut resource_holder.resource
+  {
+    let (tokio_file, metadata) = option_file_metadata
+      .take()
+      .ok_or_else(ErrBox::resource_unavailable)?;
+    return match tokio_file.try_into_std() {
+      Ok(mut std_file) => {
+        let result = f(Ok(&mut std_file));
+        resource_holder.resource = StreamResource::FsFile(Some((
+          tokio::fs::File::from_std(std_file),
+          metadata,
+        )));
+        result
+      }
+      Err(tokio_file) => {
+        resource_holder.resource =
+          StreamResource::FsFile(Some((tokio_file, metadata)));
+        Err(ErrBox::resource_unavailable())
+      }
+    };
+  }
+
+  let marker = match &resource_holder.resource {
+    StreamResource::Stdin(..) => {
+      StreamResource::Stdin(tokio::io::stdin(), StdinMetadata::default())
+    }
+    StreamResource::Stdout(..) => StreamResource::Stdout(tokio::io::stdout()),
+    StreamResource::Stderr(..) => StreamResource::Stderr(tokio::io::stderr()),
+    StreamResource::FsFile(_) => unreachable!(),
+  };
+  f(Err(marker))
+}
+
+pub fn init(i: &mut CoreIsolate, s: &Rc<State>) {
+  let t = &CoreIsolate::state(i).borrow().resource_table.clone();
+  i.register_op("op_write", s.stateful_json_op_sync(t, op_write));
+}
+
+#[derive(Deserialize)]
+struct WriteArgs {
+  rid: u32,
+}
+
+fn op_write(
+  _state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  use std::io::Write;
+
+  let args: WriteArgs = serde_json::from_value(args)?;
+  let rid = args.rid;
+  let buf = zero_copy
+    .get(0)
+    .ok_or_else(|| ErrBox::new("TypeError", "Invalid argument"))?;
+
+  let nwritten = std_file_resource(resource_table, rid, |r| match r {
+    Ok(std_file) => Ok(std_file.write(buf)?),
+    Err(StreamResource::Stdout(_)) => Ok(std::io::stdout().write(buf)?),
+    Err(StreamResource::Stderr(_)) => Ok(std::io::stderr().write(buf)?),
+    Err(_) => Err(ErrBox::bad_resource_id()),
+  })?;
+
+  // Tap every successful write so an active `op_start_tty_recording`
+  // session (see `ops::tty`) observes output as it happens. A no-op when
+  // `rid` isn't being recorded.
+  record_tty_write(rid, &buf[..nwritten]);
+
+  Ok(json!(nwritten))
+}
@@ -10,7 +10,18 @@ use deno_core::ZeroCopyBuf;
 use nix::sys::termios;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::rc::Rc;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 #[cfg(windows)]
 use winapi::shared::minwindef::DWORD;
@@ -45,6 +56,38 @@ pub fn init(i: &mut CoreIsolate, s: &Rc<State>) {
     "op_console_size",
     s.stateful_json_op_sync(t, op_console_size),
   );
+  i.register_op(
+    "op_read_tty_event",
+    s.stateful_json_op_async(t, op_read_tty_event),
+  );
+  i.register_op(
+    "op_start_tty_recording",
+    s.stateful_json_op_sync(t, op_start_tty_recording),
+  );
+  i.register_op(
+    "op_stop_tty_recording",
+    s.stateful_json_op_sync(t, op_stop_tty_recording),
+  );
+  i.register_op(
+    "op_set_tty_mode",
+    s.stateful_json_op_sync(t, op_set_tty_mode),
+  );
+  i.register_op(
+    "op_set_console_size",
+    s.stateful_json_op_sync(t, op_set_console_size),
+  );
+  i.register_op(
+    "op_tty_get_pgrp",
+    s.stateful_json_op_sync(t, op_tty_get_pgrp),
+  );
+  i.register_op(
+    "op_tty_set_pgrp",
+    s.stateful_json_op_sync(t, op_tty_set_pgrp),
+  );
+  i.register_op(
+    "op_next_console_resize",
+    s.stateful_json_op_async(t, op_next_console_resize),
+  );
 }
 
 #[derive(Deserialize)]
@@ -215,6 +258,162 @@ fn op_set_raw(
   }
 }
 
+#[derive(Deserialize, Default)]
+struct SetTtyModeArgs {
+  rid: u32,
+  #[serde(default)]
+  echo: Option<bool>,
+  #[serde(default)]
+  canonical: Option<bool>,
+  #[serde(default)]
+  signals: Option<bool>,
+  #[serde(default)]
+  flow_control: Option<bool>,
+  // Convenience preset for "cbreak"/rare mode: disables canonical mode and
+  // echo but, unlike `op_set_raw`, leaves ISIG on so Ctrl+C still raises
+  // SIGINT. Explicit `echo`/`canonical`/`signals` still win if also given.
+  #[serde(default)]
+  cbreak: Option<bool>,
+}
+
+#[cfg(windows)]
+fn set_console_mode_bit(mode: DWORD, bit: DWORD, value: bool) -> DWORD {
+  if value {
+    mode | bit
+  } else {
+    mode & !bit
+  }
+}
+
+fn op_set_tty_mode(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  state.check_unstable("Deno.setTtyMode");
+  let args: SetTtyModeArgs = serde_json::from_value(args)?;
+  let rid = args.rid;
+  let cbreak = args.cbreak.unwrap_or(false);
+  let echo = args.echo.unwrap_or(!cbreak);
+  let canonical = args.canonical.unwrap_or(!cbreak);
+  let signals = args.signals.unwrap_or(true);
+  let flow_control = args.flow_control.unwrap_or(true);
+
+  #[cfg(windows)]
+  {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::shared::minwindef::FALSE;
+    use winapi::um::{consoleapi, handleapi};
+
+    let resource_holder = resource_table
+      .get_mut::<StreamResourceHolder>(rid)
+      .ok_or_else(ErrBox::bad_resource_id)?;
+
+    // For now, only stdin.
+    let handle = match &mut resource_holder.resource {
+      StreamResource::Stdin(..) => std::io::stdin().as_raw_handle(),
+      StreamResource::FsFile(ref mut option_file_metadata) => {
+        if let Some((tokio_file, metadata)) = option_file_metadata.take() {
+          match tokio_file.try_into_std() {
+            Ok(std_file) => {
+              let raw_handle = std_file.as_raw_handle();
+              let tokio_file = tokio::fs::File::from_std(std_file);
+              resource_holder.resource =
+                StreamResource::FsFile(Some((tokio_file, metadata)));
+              raw_handle
+            }
+            Err(tokio_file) => {
+              resource_holder.resource =
+                StreamResource::FsFile(Some((tokio_file, metadata)));
+              return Err(ErrBox::resource_unavailable());
+            }
+          }
+        } else {
+          return Err(ErrBox::resource_unavailable());
+        }
+      }
+      _ => {
+        return Err(ErrBox::bad_resource_id());
+      }
+    };
+
+    if handle == handleapi::INVALID_HANDLE_VALUE {
+      return Err(ErrBox::last_os_error());
+    } else if handle.is_null() {
+      return Err(ErrBox::new("ReferenceError", "null handle"));
+    }
+
+    let mut original_mode: DWORD = 0;
+    if unsafe { consoleapi::GetConsoleMode(handle, &mut original_mode) }
+      == FALSE
+    {
+      return Err(ErrBox::last_os_error());
+    }
+
+    let mut new_mode = original_mode;
+    new_mode =
+      set_console_mode_bit(new_mode, wincon::ENABLE_LINE_INPUT, canonical);
+    new_mode = set_console_mode_bit(new_mode, wincon::ENABLE_ECHO_INPUT, echo);
+    new_mode =
+      set_console_mode_bit(new_mode, wincon::ENABLE_PROCESSED_INPUT, signals);
+
+    if unsafe { consoleapi::SetConsoleMode(handle, new_mode) } == FALSE {
+      return Err(ErrBox::last_os_error());
+    }
+
+    Ok(json!({}))
+  }
+  #[cfg(unix)]
+  {
+    use std::os::unix::io::AsRawFd;
+
+    let resource_holder = resource_table
+      .get_mut::<StreamResourceHolder>(rid)
+      .ok_or_else(ErrBox::bad_resource_id)?;
+
+    let (raw_fd, maybe_tty_mode) = match &mut resource_holder.resource {
+      StreamResource::Stdin(_, ref mut metadata) => {
+        (std::io::stdin().as_raw_fd(), &mut metadata.mode)
+      }
+      StreamResource::FsFile(Some((f, ref mut metadata))) => {
+        (f.as_raw_fd(), &mut metadata.tty.mode)
+      }
+      StreamResource::FsFile(None) => {
+        return Err(ErrBox::resource_unavailable())
+      }
+      _ => {
+        return Err(ErrBox::not_supported());
+      }
+    };
+
+    let original_mode = termios::tcgetattr(raw_fd)?;
+    let mut mode = original_mode.clone();
+    if maybe_tty_mode.is_none() {
+      // First time this resource's mode is touched; save it so
+      // `op_set_raw(rid, false)` can put things back exactly as they were.
+      // This op itself only changes flags on the current mode and never
+      // restores the saved one.
+      maybe_tty_mode.replace(original_mode);
+    }
+
+    mode.local_flags.set(termios::LocalFlags::ECHO, echo);
+    mode.local_flags.set(termios::LocalFlags::ICANON, canonical);
+    mode.local_flags.set(termios::LocalFlags::ISIG, signals);
+    mode.input_flags.set(termios::InputFlags::IXON, flow_control);
+
+    if !canonical {
+      // Match op_set_raw: read one byte at a time with no timeout once
+      // canonical (line-buffered) mode is off.
+      mode.control_chars[termios::SpecialCharacterIndices::VMIN as usize] = 1;
+      mode.control_chars[termios::SpecialCharacterIndices::VTIME as usize] = 0;
+    }
+
+    termios::tcsetattr(raw_fd, termios::SetArg::TCSADRAIN, &mode)?;
+    Ok(json!({}))
+  }
+}
+
 #[derive(Deserialize)]
 struct IsattyArgs {
   rid: u32,
@@ -260,7 +459,7 @@ struct ConsoleSizeArgs {
   rid: u32,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Copy, PartialEq)]
 struct ConsoleSize {
   columns: u32,
   rows: u32,
@@ -274,9 +473,17 @@ fn op_console_size(
 ) -> Result<Value, ErrBox> {
   state.check_unstable("Deno.consoleSize");
   let args: ConsoleSizeArgs = serde_json::from_value(args)?;
-  let rid = args.rid;
+  let size = console_size(resource_table, args.rid)?;
+  Ok(json!(size))
+}
 
-  let size = std_file_resource(resource_table, rid as u32, move |r| match r {
+// Shared by `op_console_size` and the recording ops, which need the same
+// winsize/console-buffer lookup to stamp an asciicast header.
+fn console_size(
+  resource_table: &mut ResourceTable,
+  rid: u32,
+) -> Result<ConsoleSize, ErrBox> {
+  std_file_resource(resource_table, rid, move |r| match r {
     Ok(std_file) => {
       #[cfg(windows)]
       {
@@ -322,7 +529,1150 @@ fn op_console_size(
       }
     }
     Err(_) => Err(ErrBox::bad_resource_id()),
+  })
+}
+
+// Shared by `op_set_console_size`, `op_tty_get_pgrp` and `op_tty_set_pgrp`:
+// those ops only make sense on an actual terminal, and an ioctl/tcgetpgrp/
+// tcsetpgrp on a non-tty fd fails with a raw OS error (e.g. `ENOTTY`) that
+// doesn't mean anything to script authors. Gate on `isatty` first so they
+// see the same `bad_resource_id` they'd get from any other non-tty rid.
+fn ensure_is_tty(std_file: &std::fs::File) -> Result<(), ErrBox> {
+  #[cfg(windows)]
+  {
+    use winapi::um::consoleapi;
+
+    let handle = get_windows_handle(std_file)?;
+    let mut mode: DWORD = 0;
+    if unsafe { consoleapi::GetConsoleMode(handle, &mut mode) } == 0 {
+      return Err(ErrBox::bad_resource_id());
+    }
+  }
+  #[cfg(unix)]
+  {
+    use std::os::unix::io::AsRawFd;
+    let raw_fd = std_file.as_raw_fd();
+    if unsafe { libc::isatty(raw_fd as libc::c_int) } != 1 {
+      return Err(ErrBox::bad_resource_id());
+    }
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+struct SetConsoleSizeArgs {
+  rid: u32,
+  columns: u32,
+  rows: u32,
+}
+
+// The write counterpart to `op_console_size`: lets a pseudo-terminal host
+// resize a child's controlling terminal.
+fn op_set_console_size(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  state.check_unstable("Deno.setConsoleSize");
+  let args: SetConsoleSizeArgs = serde_json::from_value(args)?;
+  let rid = args.rid;
+  let columns = args.columns;
+  let rows = args.rows;
+
+  std_file_resource(resource_table, rid, move |r| match r {
+    Ok(std_file) => {
+      ensure_is_tty(std_file)?;
+
+      #[cfg(windows)]
+      {
+        use std::os::windows::io::AsRawHandle;
+        let handle = std_file.as_raw_handle();
+
+        unsafe {
+          let size = winapi::um::wincontypes::COORD {
+            X: columns as i16,
+            Y: rows as i16,
+          };
+          if winapi::um::wincon::SetConsoleScreenBufferSize(handle, size) == 0
+          {
+            return Err(ErrBox::last_os_error());
+          }
+        }
+        Ok(())
+      }
+
+      #[cfg(unix)]
+      {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = std_file.as_raw_fd();
+        unsafe {
+          let size = libc::winsize {
+            ws_col: columns as libc::c_ushort,
+            ws_row: rows as libc::c_ushort,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+          };
+          if libc::ioctl(fd, libc::TIOCSWINSZ, &size as *const _) != 0 {
+            return Err(ErrBox::last_os_error());
+          }
+        }
+        Ok(())
+      }
+    }
+    Err(_) => Err(ErrBox::bad_resource_id()),
   })?;
 
-  Ok(json!(size))
+  Ok(json!({}))
+}
+
+#[derive(Deserialize)]
+struct TtyPgrpArgs {
+  rid: u32,
+}
+
+#[cfg(unix)]
+fn op_tty_get_pgrp(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  state.check_unstable("Deno.ttyGetPgrp");
+  let args: TtyPgrpArgs = serde_json::from_value(args)?;
+
+  let pgrp = std_file_resource(resource_table, args.rid, move |r| match r {
+    Ok(std_file) => {
+      ensure_is_tty(std_file)?;
+      use std::os::unix::io::AsRawFd;
+      nix::unistd::tcgetpgrp(std_file.as_raw_fd()).map_err(ErrBox::from)
+    }
+    Err(_) => Err(ErrBox::bad_resource_id()),
+  })?;
+
+  Ok(json!(pgrp.as_raw()))
+}
+
+#[cfg(windows)]
+fn op_tty_get_pgrp(
+  _state: &State,
+  _resource_table: &mut ResourceTable,
+  _args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  Err(ErrBox::not_supported())
+}
+
+#[derive(Deserialize)]
+struct TtySetPgrpArgs {
+  rid: u32,
+  pid: i32,
+}
+
+#[cfg(unix)]
+fn op_tty_set_pgrp(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  state.check_unstable("Deno.ttySetPgrp");
+  let args: TtySetPgrpArgs = serde_json::from_value(args)?;
+  let pid = nix::unistd::Pid::from_raw(args.pid);
+
+  std_file_resource(resource_table, args.rid, move |r| match r {
+    Ok(std_file) => {
+      ensure_is_tty(std_file)?;
+      use std::os::unix::io::AsRawFd;
+      nix::unistd::tcsetpgrp(std_file.as_raw_fd(), pid).map_err(ErrBox::from)
+    }
+    Err(_) => Err(ErrBox::bad_resource_id()),
+  })?;
+
+  Ok(json!({}))
+}
+
+#[cfg(windows)]
+fn op_tty_set_pgrp(
+  _state: &State,
+  _resource_table: &mut ResourceTable,
+  _args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  Err(ErrBox::not_supported())
+}
+
+#[derive(Deserialize)]
+struct ReadTtyEventArgs {
+  rid: u32,
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+struct KeyModifiers {
+  shift: bool,
+  alt: bool,
+  ctrl: bool,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum MouseButton {
+  Left,
+  Middle,
+  Right,
+  WheelUp,
+  WheelDown,
+  Unknown,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum TtyEvent {
+  Key {
+    code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    char: Option<char>,
+    modifiers: KeyModifiers,
+  },
+  Mouse {
+    button: MouseButton,
+    column: u16,
+    row: u16,
+    pressed: bool,
+    modifiers: KeyModifiers,
+  },
+}
+
+impl TtyEvent {
+  fn key(code: &str) -> Self {
+    TtyEvent::Key {
+      code: code.to_string(),
+      char: None,
+      modifiers: KeyModifiers::default(),
+    }
+  }
+
+  fn key_with_modifiers(code: &str, modifiers: KeyModifiers) -> Self {
+    TtyEvent::Key {
+      code: code.to_string(),
+      char: None,
+      modifiers,
+    }
+  }
+
+  fn char_key(c: char, modifiers: KeyModifiers) -> Self {
+    TtyEvent::Key {
+      code: "Char".to_string(),
+      char: Some(c),
+      modifiers,
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct ReadTtyEventResult {
+  event: Option<TtyEvent>,
+  consumed: usize,
+  incomplete: bool,
+  eof: bool,
+}
+
+// Maps the modifier parameter of a CSI sequence (as used by xterm's
+// "modifyOtherKeys"/SGR encoding, e.g. `ESC [ 1 ; 5 C`) to Shift/Alt/Ctrl.
+// The parameter is 1-based: 1 is "no modifiers", 2 is Shift, and so on.
+fn modifiers_from_param(param: u32) -> KeyModifiers {
+  let bits = param.saturating_sub(1);
+  KeyModifiers {
+    shift: bits & 0b001 != 0,
+    alt: bits & 0b010 != 0,
+    ctrl: bits & 0b100 != 0,
+  }
+}
+
+// vt220-style numeric codes for the `CSI <n> ~` family.
+fn tilde_code(n: u32) -> Option<&'static str> {
+  Some(match n {
+    1 => "Home",
+    2 => "Insert",
+    3 => "Delete",
+    4 => "End",
+    5 => "PageUp",
+    6 => "PageDown",
+    11 => "F1",
+    12 => "F2",
+    13 => "F3",
+    14 => "F4",
+    15 => "F5",
+    17 => "F6",
+    18 => "F7",
+    19 => "F8",
+    20 => "F9",
+    21 => "F10",
+    23 => "F11",
+    24 => "F12",
+    _ => return None,
+  })
+}
+
+// Decodes the `Pb` parameter of an SGR (`CSI <` ... `M`/`m`) mouse report
+// into a button and the modifier keys held during the event.
+fn mouse_button_from_param(pb: u32) -> (MouseButton, KeyModifiers) {
+  let modifiers = KeyModifiers {
+    shift: pb & 4 != 0,
+    alt: pb & 8 != 0,
+    ctrl: pb & 16 != 0,
+  };
+  let button = if pb & 64 != 0 {
+    if pb & 1 == 0 {
+      MouseButton::WheelUp
+    } else {
+      MouseButton::WheelDown
+    }
+  } else {
+    match pb & 0x3 {
+      0 => MouseButton::Left,
+      1 => MouseButton::Middle,
+      2 => MouseButton::Right,
+      _ => MouseButton::Unknown,
+    }
+  };
+  (button, modifiers)
+}
+
+// Parses a single structured input event out of `buf`, returning the parsed
+// event (if any), how many bytes it consumed, and whether `buf` ended in the
+// middle of a sequence. `op_read_tty_event` is the caller: on `incomplete`
+// it stashes `buf[consumed..]` in `TTY_EVENT_BUFFERS` and prepends it to the
+// next read instead of discarding it.
+fn parse_tty_event(buf: &[u8]) -> (Option<TtyEvent>, usize, bool) {
+  if buf.is_empty() {
+    return (None, 0, true);
+  }
+
+  if buf[0] != 0x1b {
+    return parse_key_byte(buf);
+  }
+
+  // A lone ESC with nothing following it (yet) is the Esc key itself.
+  if buf.len() == 1 {
+    return (Some(TtyEvent::key("Esc")), 1, false);
+  }
+
+  if buf[1] == b'[' {
+    return parse_csi(buf);
+  }
+
+  // Unrecognized escape sequence; treat the ESC on its own as the Esc key
+  // and let the remaining bytes be reparsed on the next call.
+  (Some(TtyEvent::key("Esc")), 1, false)
+}
+
+// Parses a plain (non-escape) key: control characters, Backspace, and
+// UTF-8-encoded characters (including multibyte ones).
+fn parse_key_byte(buf: &[u8]) -> (Option<TtyEvent>, usize, bool) {
+  let b = buf[0];
+  match b {
+    0x7f => (Some(TtyEvent::key("Backspace")), 1, false),
+    0x00..=0x1f => {
+      // Ctrl+letter: Ctrl+A is 0x01, Ctrl+Z is 0x1a, etc.
+      let c = (b | 0x60) as char;
+      let modifiers = KeyModifiers {
+        ctrl: true,
+        ..Default::default()
+      };
+      (Some(TtyEvent::char_key(c, modifiers)), 1, false)
+    }
+    _ => {
+      let width = utf8_width(b);
+      if buf.len() < width {
+        return (None, 0, true);
+      }
+      match std::str::from_utf8(&buf[..width]) {
+        Ok(s) => {
+          let c = s.chars().next().unwrap();
+          (
+            Some(TtyEvent::char_key(c, KeyModifiers::default())),
+            width,
+            false,
+          )
+        }
+        Err(_) => (Some(TtyEvent::key("Unknown")), 1, false),
+      }
+    }
+  }
+}
+
+fn utf8_width(first_byte: u8) -> usize {
+  if first_byte & 0x80 == 0 {
+    1
+  } else if first_byte & 0xe0 == 0xc0 {
+    2
+  } else if first_byte & 0xf0 == 0xe0 {
+    3
+  } else if first_byte & 0xf8 == 0xf0 {
+    4
+  } else {
+    1
+  }
+}
+
+// Parses `ESC [ ...` (CSI) sequences: cursor keys, Home/End, the numeric
+// `~`-terminated family, and SGR mouse reports.
+fn parse_csi(buf: &[u8]) -> (Option<TtyEvent>, usize, bool) {
+  // buf[0] == ESC, buf[1] == '['
+  let mut i = 2;
+  let mouse_report = buf.len() > i && buf[i] == b'<';
+  if mouse_report {
+    i += 1;
+  }
+
+  let params_start = i;
+  while i < buf.len() && (0x30..=0x3f).contains(&buf[i]) {
+    i += 1;
+  }
+  let params_end = i;
+  while i < buf.len() && (0x20..=0x2f).contains(&buf[i]) {
+    i += 1;
+  }
+
+  if i >= buf.len() {
+    return (None, 0, true);
+  }
+  let final_byte = buf[i];
+  if !(0x40..=0x7e).contains(&final_byte) {
+    return (None, 0, true);
+  }
+
+  let consumed = i + 1;
+  let params: Vec<u32> = buf[params_start..params_end]
+    .split(|&b| b == b';')
+    .filter(|p| !p.is_empty())
+    .map(|p| std::str::from_utf8(p).ok().and_then(|s| s.parse().ok()).unwrap_or(0))
+    .collect();
+
+  if mouse_report {
+    return parse_sgr_mouse(&params, final_byte, consumed);
+  }
+
+  let modifiers = params
+    .get(1)
+    .copied()
+    .map(modifiers_from_param)
+    .unwrap_or_default();
+
+  let event = match final_byte {
+    b'A' => Some(TtyEvent::key_with_modifiers("Up", modifiers)),
+    b'B' => Some(TtyEvent::key_with_modifiers("Down", modifiers)),
+    b'C' => Some(TtyEvent::key_with_modifiers("Right", modifiers)),
+    b'D' => Some(TtyEvent::key_with_modifiers("Left", modifiers)),
+    b'H' => Some(TtyEvent::key_with_modifiers("Home", modifiers)),
+    b'F' => Some(TtyEvent::key_with_modifiers("End", modifiers)),
+    b'~' => params
+      .first()
+      .copied()
+      .and_then(tilde_code)
+      .map(|code| TtyEvent::key_with_modifiers(code, modifiers)),
+    _ => None,
+  };
+
+  (event.or_else(|| Some(TtyEvent::key("Unknown"))), consumed, false)
+}
+
+fn parse_sgr_mouse(
+  params: &[u32],
+  final_byte: u8,
+  consumed: usize,
+) -> (Option<TtyEvent>, usize, bool) {
+  if params.len() < 3 {
+    return (Some(TtyEvent::key("Unknown")), consumed, false);
+  }
+  let (button, modifiers) = mouse_button_from_param(params[0]);
+  let event = TtyEvent::Mouse {
+    button,
+    column: params[1] as u16,
+    row: params[2] as u16,
+    pressed: final_byte == b'M',
+    modifiers,
+  };
+  (Some(event), consumed, false)
+}
+
+// A raw read handle extracted from the resource table. Owned (not
+// borrowed) so it can be moved into `spawn_blocking` without holding the
+// resource table — or the isolate's event loop — for the duration of the
+// (blocking) read.
+enum ReadSource {
+  Stdin,
+  File(std::fs::File),
+}
+
+impl ReadSource {
+  fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+    use std::io::Read;
+    match self {
+      ReadSource::Stdin => std::io::stdin().lock().read(buf),
+      ReadSource::File(f) => (&*f).read(buf),
+    }
+  }
+}
+
+fn tty_read_source(
+  resource_table: &mut ResourceTable,
+  rid: u32,
+) -> Result<ReadSource, ErrBox> {
+  let resource_holder = resource_table
+    .get_mut::<StreamResourceHolder>(rid)
+    .ok_or_else(ErrBox::bad_resource_id)?;
+
+  match &mut resource_holder.resource {
+    StreamResource::Stdin(..) => Ok(ReadSource::Stdin),
+    StreamResource::FsFile(ref mut option_file_metadata) => {
+      if let Some((tokio_file, metadata)) = option_file_metadata.take() {
+        match tokio_file.try_into_std() {
+          Ok(std_file) => {
+            // Clone the fd so the original can go straight back into the
+            // resource table; the clone is what gets moved into
+            // `spawn_blocking`.
+            let cloned = std_file.try_clone()?;
+            let tokio_file = tokio::fs::File::from_std(std_file);
+            *option_file_metadata = Some((tokio_file, metadata));
+            Ok(ReadSource::File(cloned))
+          }
+          Err(tokio_file) => {
+            *option_file_metadata = Some((tokio_file, metadata));
+            Err(ErrBox::resource_unavailable())
+          }
+        }
+      } else {
+        Err(ErrBox::resource_unavailable())
+      }
+    }
+    _ => Err(ErrBox::bad_resource_id()),
+  }
+}
+
+// Bytes left over from a previous `op_read_tty_event` call that ended
+// mid-sequence, keyed by rid, so the next call picks up where the last one
+// left off instead of silently dropping them.
+thread_local! {
+  static TTY_EVENT_BUFFERS: RefCell<HashMap<u32, Vec<u8>>> =
+    RefCell::new(HashMap::new());
+}
+
+fn op_read_tty_event(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Pin<Box<dyn Future<Output = Result<Value, ErrBox>>>> {
+  state.check_unstable("Deno.readTtyEvent");
+
+  let setup = (|| -> Result<(u32, ReadSource), ErrBox> {
+    let args: ReadTtyEventArgs = serde_json::from_value(args)?;
+    let rid = args.rid;
+    let source = tty_read_source(resource_table, rid)?;
+    Ok((rid, source))
+  })();
+
+  Box::pin(async move {
+    let (rid, source) = setup?;
+
+    let mut buf = TTY_EVENT_BUFFERS
+      .with(|buffers| buffers.borrow_mut().remove(&rid))
+      .unwrap_or_default();
+
+    // A burst (e.g. a paste) can land more than one event's worth of bytes
+    // in a single read; the previous call stashes whatever's left after
+    // the first event and returns. Parse that leftover *before* blocking
+    // on new input, or every event after the first in a batch would wait
+    // on a keypress that may never come.
+    let (event, consumed, incomplete) = parse_tty_event(&buf);
+    let (event, consumed, incomplete, eof) = if event.is_some() {
+      (event, consumed, incomplete, false)
+    } else {
+      // The actual read blocks until a byte arrives; run it on the
+      // blocking thread pool so it doesn't freeze the isolate's event
+      // loop.
+      let mut chunk = [0u8; 64];
+      let (chunk, n) = tokio::task::spawn_blocking(move || {
+        let n = source.read(&mut chunk)?;
+        Ok::<_, std::io::Error>((chunk, n))
+      })
+      .await
+      .map_err(|_| ErrBox::new("Error", "tty read task panicked"))??;
+
+      if n == 0 {
+        // EOF. Whatever's left in `buf` will never be completed by more
+        // bytes, so report EOF rather than leaving the caller to spin on
+        // `incomplete` against a read that keeps returning nothing.
+        (None, 0, false, true)
+      } else {
+        buf.extend_from_slice(&chunk[..n]);
+        let (event, consumed, incomplete) = parse_tty_event(&buf);
+        (event, consumed, incomplete, false)
+      }
+    };
+
+    let remaining = buf.split_off(consumed.min(buf.len()));
+    if !remaining.is_empty() && !eof {
+      TTY_EVENT_BUFFERS.with(|buffers| {
+        buffers.borrow_mut().insert(rid, remaining);
+      });
+    }
+
+    let result = ReadTtyEventResult {
+      event,
+      consumed,
+      incomplete,
+      eof,
+    };
+    Ok(json!(result))
+  })
+}
+
+// Active asciicast v2 recordings, keyed by the rid of the tty-backed
+// resource being recorded. Kept out of the resource table since a
+// recording isn't itself a resource that JS code reads from or writes to;
+// it's a passive tap on writes to `rid`.
+thread_local! {
+  static TTY_RECORDINGS: RefCell<HashMap<u32, TtyRecording>> =
+    RefCell::new(HashMap::new());
+}
+
+struct TtyRecording {
+  file: std::fs::File,
+  // Per the asciicast v2 spec, event timing is measured with a monotonic
+  // clock "from the first event" — not from when recording was started —
+  // so this is set lazily by the first `write_event` rather than eagerly
+  // in `op_start_tty_recording`.
+  start: Option<Instant>,
+}
+
+impl TtyRecording {
+  // Journals one "o" (output) event, per the asciicast v2 event stream
+  // format: `[<seconds-since-start>, "o", "<chunk>"]`.
+  fn write_event(&mut self, data: &[u8]) -> Result<(), ErrBox> {
+    use std::io::Write;
+    let start = *self.start.get_or_insert_with(Instant::now);
+    let elapsed = start.elapsed().as_secs_f64();
+    let event = (elapsed, "o", String::from_utf8_lossy(data));
+    writeln!(self.file, "{}", serde_json::to_string(&event)?)?;
+    Ok(())
+  }
+}
+
+// Called from `ops::io::op_write` for every chunk written to `rid`, so an
+// active recording observes output as it happens. A no-op if `rid` isn't
+// being recorded.
+pub(crate) fn record_tty_write(rid: u32, data: &[u8]) {
+  TTY_RECORDINGS.with(|recordings| {
+    if let Some(recording) = recordings.borrow_mut().get_mut(&rid) {
+      let _ = recording.write_event(data);
+    }
+  });
+}
+
+#[derive(Deserialize)]
+struct StartTtyRecordingArgs {
+  rid: u32,
+  path: String,
+  width: Option<u32>,
+  rows: Option<u32>,
+  env: HashMap<String, String>,
+}
+
+fn op_start_tty_recording(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  use std::io::Write;
+
+  state.check_unstable("Deno.startTtyRecording");
+  let args: StartTtyRecordingArgs = serde_json::from_value(args)?;
+
+  let (width, rows) = match (args.width, args.rows) {
+    (Some(width), Some(rows)) => (width, rows),
+    _ => {
+      let size = console_size(resource_table, args.rid)?;
+      (size.columns, size.rows)
+    }
+  };
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_err(|_| ErrBox::new("TypeError", "system clock before unix epoch"))?
+    .as_secs();
+
+  let header = json!({
+    "version": 2,
+    "width": width,
+    "height": rows,
+    "timestamp": timestamp,
+    "env": args.env,
+  });
+
+  let mut file = std::fs::File::create(&args.path)?;
+  writeln!(file, "{}", header)?;
+
+  TTY_RECORDINGS.with(|recordings| {
+    recordings.borrow_mut().insert(
+      args.rid,
+      TtyRecording { file, start: None },
+    );
+  });
+
+  Ok(json!({}))
+}
+
+#[derive(Deserialize)]
+struct StopTtyRecordingArgs {
+  rid: u32,
+}
+
+fn op_stop_tty_recording(
+  state: &State,
+  _resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Result<Value, ErrBox> {
+  state.check_unstable("Deno.stopTtyRecording");
+  let args: StopTtyRecordingArgs = serde_json::from_value(args)?;
+
+  let had_recording = TTY_RECORDINGS
+    .with(|recordings| recordings.borrow_mut().remove(&args.rid))
+    .is_some();
+  if !had_recording {
+    return Err(ErrBox::bad_resource_id());
+  }
+
+  Ok(json!({}))
+}
+
+#[derive(Deserialize)]
+struct NextConsoleResizeArgs {
+  rid: u32,
+}
+
+// One independent "wake me when this rid's size changes" registration.
+// Shared between the future returned to the caller and the global resize
+// watcher so several outstanding `op_next_console_resize(rid)` calls for
+// the same rid are all resolved off a single OS notification.
+struct ResizeWaiter {
+  rid: u32,
+  #[cfg(unix)]
+  fd: std::os::unix::io::RawFd,
+  #[cfg(windows)]
+  handle: std::os::windows::io::RawHandle,
+  baseline: ConsoleSize,
+  ready: Cell<Option<ConsoleSize>>,
+  waker: RefCell<Option<Waker>>,
+}
+
+thread_local! {
+  static RESIZE_WAITERS: RefCell<HashMap<u32, Vec<Rc<ResizeWaiter>>>> =
+    RefCell::new(HashMap::new());
+}
+
+// Re-reads the console size directly from a raw fd/handle, bypassing the
+// resource table — the global resize watcher doesn't have one to borrow.
+#[cfg(unix)]
+fn read_console_size(fd: std::os::unix::io::RawFd) -> Result<ConsoleSize, ErrBox> {
+  unsafe {
+    let mut size: libc::winsize = std::mem::zeroed();
+    if libc::ioctl(fd, libc::TIOCGWINSZ, &mut size as *mut _) != 0 {
+      return Err(ErrBox::last_os_error());
+    }
+    Ok(ConsoleSize {
+      columns: size.ws_col as u32,
+      rows: size.ws_row as u32,
+    })
+  }
+}
+
+#[cfg(windows)]
+fn read_console_size(
+  handle: std::os::windows::io::RawHandle,
+) -> Result<ConsoleSize, ErrBox> {
+  unsafe {
+    let mut bufinfo: winapi::um::wincon::CONSOLE_SCREEN_BUFFER_INFO =
+      std::mem::zeroed();
+    if winapi::um::wincon::GetConsoleScreenBufferInfo(handle, &mut bufinfo)
+      == 0
+    {
+      return Err(ErrBox::last_os_error());
+    }
+    Ok(ConsoleSize {
+      columns: bufinfo.dwSize.X as u32,
+      rows: bufinfo.dwSize.Y as u32,
+    })
+  }
+}
+
+// Checks every outstanding waiter against its stored fd/handle and wakes
+// (and drops) the ones whose size changed since they registered. Installed
+// once per thread and driven by SIGWINCH (Unix) or a console input
+// watcher thread (Windows); see `ensure_resize_watcher_installed`.
+fn poll_resize_waiters() {
+  RESIZE_WAITERS.with(|waiters| {
+    let mut waiters = waiters.borrow_mut();
+    for rid_waiters in waiters.values_mut() {
+      rid_waiters.retain(|waiter| {
+        #[cfg(unix)]
+        let current = read_console_size(waiter.fd);
+        #[cfg(windows)]
+        let current = read_console_size(waiter.handle);
+
+        match current {
+          Ok(size) if size != waiter.baseline => {
+            waiter.ready.set(Some(size));
+            if let Some(waker) = waiter.waker.borrow_mut().take() {
+              waker.wake();
+            }
+            false
+          }
+          _ => true,
+        }
+      });
+    }
+  });
+}
+
+#[cfg(unix)]
+fn ensure_resize_watcher_installed() {
+  thread_local! {
+    static INSTALLED: Cell<bool> = Cell::new(false);
+  }
+  INSTALLED.with(|installed| {
+    if installed.get() {
+      return;
+    }
+    installed.set(true);
+
+    tokio::task::spawn_local(async move {
+      let mut sigwinch =
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::window_change()) {
+          Ok(sigwinch) => sigwinch,
+          Err(_) => return,
+        };
+      while sigwinch.recv().await.is_some() {
+        poll_resize_waiters();
+      }
+    });
+  });
+}
+
+#[cfg(windows)]
+fn ensure_resize_watcher_installed() {
+  thread_local! {
+    static INSTALLED: Cell<bool> = Cell::new(false);
+  }
+  INSTALLED.with(|installed| {
+    if installed.get() {
+      return;
+    }
+    installed.set(true);
+
+    // The console input handle delivers WINDOW_BUFFER_SIZE_EVENT records
+    // whenever the screen buffer is resized; poll it on a dedicated thread
+    // since ReadConsoleInput blocks, then hop back onto the tokio runtime
+    // to wake any pending futures.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    std::thread::spawn(move || loop {
+      use winapi::um::consoleapi::ReadConsoleInputW;
+      use winapi::um::processenv::GetStdHandle;
+      use winapi::um::wincon::{INPUT_RECORD, WINDOW_BUFFER_SIZE_EVENT};
+      use winapi::um::winbase::STD_INPUT_HANDLE;
+
+      unsafe {
+        let handle = GetStdHandle(STD_INPUT_HANDLE);
+        let mut record: INPUT_RECORD = std::mem::zeroed();
+        let mut read = 0;
+        if ReadConsoleInputW(handle, &mut record, 1, &mut read) == 0 {
+          return;
+        }
+        if record.EventType == WINDOW_BUFFER_SIZE_EVENT && tx.send(()).is_err()
+        {
+          return;
+        }
+      }
+    });
+
+    tokio::task::spawn_local(async move {
+      while rx.recv().await.is_some() {
+        poll_resize_waiters();
+      }
+    });
+  });
+}
+
+fn op_next_console_resize(
+  state: &State,
+  resource_table: &mut ResourceTable,
+  args: Value,
+  _zero_copy: &mut [ZeroCopyBuf],
+) -> Pin<Box<dyn Future<Output = Result<Value, ErrBox>>>> {
+  state.check_unstable("Deno.nextConsoleResize");
+
+  let waiter = (|| -> Result<Rc<ResizeWaiter>, ErrBox> {
+    let args: NextConsoleResizeArgs = serde_json::from_value(args)?;
+    let rid = args.rid;
+    let baseline = console_size(resource_table, rid)?;
+
+    #[cfg(unix)]
+    let waiter = Rc::new(ResizeWaiter {
+      rid,
+      fd: tty_raw_fd(resource_table, rid)?,
+      baseline,
+      ready: Cell::new(None),
+      waker: RefCell::new(None),
+    });
+    #[cfg(windows)]
+    let waiter = Rc::new(ResizeWaiter {
+      rid,
+      handle: tty_raw_handle(resource_table, rid)?,
+      baseline,
+      ready: Cell::new(None),
+      waker: RefCell::new(None),
+    });
+
+    ensure_resize_watcher_installed();
+    RESIZE_WAITERS.with(|waiters| {
+      waiters
+        .borrow_mut()
+        .entry(rid)
+        .or_insert_with(Vec::new)
+        .push(waiter.clone());
+    });
+
+    Ok(waiter)
+  })();
+
+  Box::pin(async move {
+    let waiter = waiter?;
+    NextConsoleResizeFuture { waiter }.await
+  })
+}
+
+#[cfg(unix)]
+fn tty_raw_fd(
+  resource_table: &mut ResourceTable,
+  rid: u32,
+) -> Result<std::os::unix::io::RawFd, ErrBox> {
+  use std::os::unix::io::AsRawFd;
+  std_file_resource(resource_table, rid, move |r| match r {
+    Ok(std_file) => Ok(std_file.as_raw_fd()),
+    Err(_) => Err(ErrBox::bad_resource_id()),
+  })
+}
+
+#[cfg(windows)]
+fn tty_raw_handle(
+  resource_table: &mut ResourceTable,
+  rid: u32,
+) -> Result<std::os::windows::io::RawHandle, ErrBox> {
+  use std::os::windows::io::AsRawHandle;
+  std_file_resource(resource_table, rid, move |r| match r {
+    Ok(std_file) => Ok(std_file.as_raw_handle()),
+    Err(_) => Err(ErrBox::bad_resource_id()),
+  })
+}
+
+struct NextConsoleResizeFuture {
+  waiter: Rc<ResizeWaiter>,
+}
+
+impl Future for NextConsoleResizeFuture {
+  type Output = Result<Value, ErrBox>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    if let Some(size) = self.waiter.ready.take() {
+      return Poll::Ready(Ok(json!(size)));
+    }
+    *self.waiter.waker.borrow_mut() = Some(cx.waker().clone());
+    Poll::Pending
+  }
+}
+
+// `poll_resize_waiters` only removes a waiter once its console actually
+// resizes, so a future dropped beforehand (the caller's op promise was
+// never awaited to completion, e.g. it lost a `Promise.race`) would
+// otherwise leak its `Rc<ResizeWaiter>` in `RESIZE_WAITERS` for as long as
+// the rid never resizes again. Prune it here instead.
+impl Drop for NextConsoleResizeFuture {
+  fn drop(&mut self) {
+    RESIZE_WAITERS.with(|waiters| {
+      let mut waiters = waiters.borrow_mut();
+      if let Some(rid_waiters) = waiters.get_mut(&self.waiter.rid) {
+        rid_waiters.retain(|w| !Rc::ptr_eq(w, &self.waiter));
+        if rid_waiters.is_empty() {
+          waiters.remove(&self.waiter.rid);
+        }
+      }
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key_code(event: &Option<TtyEvent>) -> &str {
+    match event {
+      Some(TtyEvent::Key { code, .. }) => code,
+      _ => panic!("expected a Key event, got {:?}", event.is_some()),
+    }
+  }
+
+  #[test]
+  fn parses_plain_ascii_char() {
+    let (event, consumed, incomplete) = parse_tty_event(b"a");
+    assert_eq!(consumed, 1);
+    assert!(!incomplete);
+    match event {
+      Some(TtyEvent::Key {
+        code, char, modifiers, ..
+      }) => {
+        assert_eq!(code, "Char");
+        assert_eq!(char, Some('a'));
+        assert!(!modifiers.ctrl);
+      }
+      other => panic!("unexpected event: {:?}", other.is_some()),
+    }
+  }
+
+  #[test]
+  fn parses_ctrl_letter() {
+    let (event, consumed, incomplete) = parse_tty_event(&[0x01]);
+    assert_eq!(consumed, 1);
+    assert!(!incomplete);
+    match event {
+      Some(TtyEvent::Key {
+        code, char, modifiers, ..
+      }) => {
+        assert_eq!(code, "Char");
+        assert_eq!(char, Some('a'));
+        assert!(modifiers.ctrl);
+      }
+      other => panic!("unexpected event: {:?}", other.is_some()),
+    }
+  }
+
+  #[test]
+  fn parses_backspace_and_lone_esc() {
+    let (event, consumed, incomplete) = parse_tty_event(&[0x7f]);
+    assert_eq!((consumed, incomplete), (1, false));
+    assert_eq!(key_code(&event), "Backspace");
+
+    let (event, consumed, incomplete) = parse_tty_event(&[0x1b]);
+    assert_eq!((consumed, incomplete), (1, false));
+    assert_eq!(key_code(&event), "Esc");
+  }
+
+  #[test]
+  fn parses_multibyte_utf8_char() {
+    let bytes = "é".as_bytes();
+    let (event, consumed, incomplete) = parse_tty_event(bytes);
+    assert_eq!(consumed, bytes.len());
+    assert!(!incomplete);
+    match event {
+      Some(TtyEvent::Key { char, .. }) => assert_eq!(char, Some('é')),
+      other => panic!("unexpected event: {:?}", other.is_some()),
+    }
+  }
+
+  #[test]
+  fn incomplete_utf8_char_requests_more_bytes() {
+    let bytes = "é".as_bytes();
+    let (event, consumed, incomplete) = parse_tty_event(&bytes[..1]);
+    assert!(event.is_none());
+    assert_eq!(consumed, 0);
+    assert!(incomplete);
+  }
+
+  #[test]
+  fn parses_arrow_keys() {
+    let (event, consumed, incomplete) = parse_tty_event(b"\x1b[A");
+    assert_eq!((consumed, incomplete), (3, false));
+    assert_eq!(key_code(&event), "Up");
+  }
+
+  #[test]
+  fn parses_arrow_key_with_ctrl_modifier() {
+    // ESC [ 1 ; 5 C == Ctrl+Right in the xterm modifyOtherKeys encoding.
+    let (event, consumed, incomplete) = parse_tty_event(b"\x1b[1;5C");
+    assert_eq!((consumed, incomplete), (6, false));
+    match event {
+      Some(TtyEvent::Key {
+        code, modifiers, ..
+      }) => {
+        assert_eq!(code, "Right");
+        assert!(modifiers.ctrl);
+        assert!(!modifiers.shift);
+        assert!(!modifiers.alt);
+      }
+      other => panic!("unexpected event: {:?}", other.is_some()),
+    }
+  }
+
+  #[test]
+  fn parses_tilde_family() {
+    let (event, consumed, incomplete) = parse_tty_event(b"\x1b[5~");
+    assert_eq!((consumed, incomplete), (4, false));
+    assert_eq!(key_code(&event), "PageUp");
+
+    let (event, _, _) = parse_tty_event(b"\x1b[15~");
+    assert_eq!(key_code(&event), "F5");
+  }
+
+  #[test]
+  fn incomplete_csi_sequence_requests_more_bytes() {
+    let (event, consumed, incomplete) = parse_tty_event(b"\x1b[1;5");
+    assert!(event.is_none());
+    assert_eq!(consumed, 0);
+    assert!(incomplete);
+  }
+
+  #[test]
+  fn parses_sgr_mouse_press_and_release() {
+    let (event, consumed, incomplete) = parse_tty_event(b"\x1b[<0;10;20M");
+    assert_eq!(incomplete, false);
+    assert_eq!(consumed, 11);
+    match event {
+      Some(TtyEvent::Mouse {
+        button,
+        column,
+        row,
+        pressed,
+        ..
+      }) => {
+        assert!(matches!(button, MouseButton::Left));
+        assert_eq!(column, 10);
+        assert_eq!(row, 20);
+        assert!(pressed);
+      }
+      other => panic!("unexpected event: {:?}", other.is_some()),
+    }
+
+    let (event, _, _) = parse_tty_event(b"\x1b[<0;10;20m");
+    match event {
+      Some(TtyEvent::Mouse { pressed, .. }) => assert!(!pressed),
+      other => panic!("unexpected event: {:?}", other.is_some()),
+    }
+  }
+
+  #[test]
+  fn mouse_button_from_param_decodes_wheel_and_modifiers() {
+    let (button, modifiers) = mouse_button_from_param(64);
+    assert!(matches!(button, MouseButton::WheelUp));
+    assert!(!modifiers.shift && !modifiers.alt && !modifiers.ctrl);
+
+    let (button, modifiers) = mouse_button_from_param(65);
+    assert!(matches!(button, MouseButton::WheelDown));
+
+    let (button, modifiers) = mouse_button_from_param(2 | 4 | 8 | 16);
+    assert!(matches!(button, MouseButton::Right));
+    assert!(modifiers.shift && modifiers.alt && modifiers.ctrl);
+  }
 }